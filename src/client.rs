@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+const API_BASE: &str = "https://api.tailscale.com/api/v2";
+const OAUTH_TOKEN_URL: &str = "https://api.tailscale.com/api/v2/oauth/token";
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// A bearer token with a known expiry, as returned by Tailscale's OAuth token endpoint.
+#[derive(Debug, Clone)]
+pub struct Expiring {
+    pub access_token: String,
+    pub expires: DateTime<Utc>
+}
+
+impl Expiring {
+    pub fn is_expired(&self) -> bool {
+        self.expires < Utc::now()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64
+}
+
+/// How the client authenticates to the Tailscale API.
+enum Auth {
+    /// A long-lived API key, used as-is.
+    Static(String),
+    /// OAuth client credentials, exchanged for a short-lived access token that
+    /// gets transparently refreshed once it expires.
+    OAuth {
+        client_id: String,
+        client_secret: String,
+        token: Mutex<Option<Expiring>>
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Device {
+    pub hostname: String,
+
+    #[serde(with = "date_format")]
+    pub expires: chrono::DateTime<chrono::Utc>
+}
+
+#[derive(Debug, Deserialize)]
+struct Devices {
+    devices: Vec<Device>
+}
+
+mod date_format {
+    use chrono::DateTime;
+    use serde::{self, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<chrono::Utc>, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let s = String::deserialize(deserializer)?;
+        let dt = DateTime::parse_from_rfc3339(&s).map_err(serde::de::Error::custom)?.with_timezone(&chrono::Utc);
+        Ok(dt)
+    }
+}
+
+/// A thin, typed wrapper around the subset of the Tailscale API this tool needs.
+///
+/// Built once per run with a crate-identifying User-Agent and gzip enabled, so
+/// all requests share connection pooling and report HTTP errors (e.g. 401/403
+/// from an expired token) as a clean `Err` instead of a serde parse failure on
+/// an error body.
+pub struct TailscaleClient {
+    http: Client,
+    base_url: String,
+    tailnet: String,
+    auth: Auth
+}
+
+impl TailscaleClient {
+    /// Build a client authenticated with a static API key.
+    pub fn new(tailnet: String, token: String) -> Result<Self> {
+        Self::build(tailnet, Auth::Static(token))
+    }
+
+    /// Build a client authenticated with OAuth client credentials, falling
+    /// back to `token` if `client_id`/`client_secret` aren't both set.
+    pub fn with_oauth(
+        tailnet: String,
+        token: String,
+        client_id: Option<String>,
+        client_secret: Option<String>
+    ) -> Result<Self> {
+        let auth = match (client_id, client_secret) {
+            (Some(client_id), Some(client_secret)) => Auth::OAuth {
+                client_id,
+                client_secret,
+                token: Mutex::new(None)
+            },
+            _ => Auth::Static(token)
+        };
+
+        Self::build(tailnet, auth)
+    }
+
+    fn build(tailnet: String, auth: Auth) -> Result<Self> {
+        let http = Client::builder()
+            .user_agent(USER_AGENT)
+            .gzip(true)
+            .build()
+            .context("building Tailscale HTTP client")?;
+
+        Ok(Self {
+            http,
+            base_url: API_BASE.to_string(),
+            tailnet,
+            auth
+        })
+    }
+
+    /// Fetch/refresh the OAuth access token if it's missing or expired, and
+    /// return the bearer token to use for the next request.
+    async fn bearer_token(&self) -> Result<String> {
+        let (client_id, client_secret, token) = match &self.auth {
+            Auth::Static(token) => return Ok(token.clone()),
+            Auth::OAuth { client_id, client_secret, token } => (client_id, client_secret, token)
+        };
+
+        let mut guard = token.lock().await;
+
+        if guard.as_ref().map(Expiring::is_expired).unwrap_or(true) {
+            let response = self
+                .http
+                .post(OAUTH_TOKEN_URL)
+                .form(&[
+                    ("client_id", client_id.as_str()),
+                    ("client_secret", client_secret.as_str()),
+                    ("grant_type", "client_credentials")
+                ])
+                .send()
+                .await
+                .context("requesting OAuth access token")?
+                .error_for_status()
+                .context("Tailscale OAuth endpoint returned an error status")?;
+
+            let body: TokenResponse = response.json().await.context("parsing OAuth token response")?;
+
+            *guard = Some(Expiring {
+                access_token: body.access_token,
+                expires: Utc::now() + Duration::seconds(body.expires_in)
+            });
+        }
+
+        Ok(guard.as_ref().expect("token was just populated").access_token.clone())
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response> {
+        let url = format!("{}/tailnet/{}/{}", self.base_url, self.tailnet, path);
+        let bearer = self.bearer_token().await?;
+
+        self.http
+            .get(url)
+            .bearer_auth(bearer)
+            .send()
+            .await
+            .context("sending request to Tailscale API")?
+            .error_for_status()
+            .context("Tailscale API returned an error status")
+    }
+
+    /// The devices registered on this tailnet.
+    pub async fn devices(&self) -> Result<Vec<Device>> {
+        let res = self.get("devices").await?;
+        let body: Devices = res.json().await.context("parsing devices response")?;
+        Ok(body.devices)
+    }
+
+    /// The tailnet's current ACL, parsed as JSON.
+    pub async fn acl(&self) -> Result<serde_json::Value> {
+        let res = self.get("acl").await?;
+        res.json().await.context("parsing ACL response")
+    }
+
+    /// The tailnet's configured DNS nameservers.
+    pub async fn dns_nameservers(&self) -> Result<serde_json::Value> {
+        let res = self.get("dns/nameservers").await?;
+        res.json().await.context("parsing DNS nameservers response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_expired_flips_around_expires() {
+        let still_valid = Expiring {
+            access_token: "token".to_string(),
+            expires: Utc::now() + Duration::seconds(60)
+        };
+        assert!(!still_valid.is_expired());
+
+        let lapsed = Expiring {
+            access_token: "token".to_string(),
+            expires: Utc::now() - Duration::seconds(60)
+        };
+        assert!(lapsed.is_expired());
+    }
+
+    #[tokio::test]
+    async fn devices_turns_error_status_into_clean_err() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/tailnet/example.ts.net/devices"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("not valid JSON"))
+            .mount(&server)
+            .await;
+
+        let client = TailscaleClient {
+            http: Client::new(),
+            base_url: server.uri(),
+            tailnet: "example.ts.net".to_string(),
+            auth: Auth::Static("expired-token".to_string())
+        };
+
+        let err = client.devices().await.expect_err("401 should surface as an error");
+        assert!(
+            format!("{err:#}").contains("error status"),
+            "expected a clean HTTP error, got: {err:#}"
+        );
+    }
+}
@@ -1,24 +1,58 @@
+use std::collections::HashMap;
+
+use clap::Parser;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
-struct Device {
-    hostname: String,
+mod client;
+mod notify;
+mod state;
 
-    #[serde(with = "date_format")]
-    expires: chrono::DateTime<chrono::Utc>
-}
+use client::{Device, TailscaleClient};
+use notify::{DesktopSink, NotificationSink, PushoverSink, SmtpSink};
+use state::NotificationState;
 
-#[derive(Debug, Deserialize)]
-struct Devices {
-    devices: Vec<Device>
+#[derive(Debug, Parser)]
+#[command(author, version, about)]
+struct Args {
+    /// Send notifications even if we already alerted on this expiry bucket.
+    #[arg(long)]
+    force: bool,
+    /// Keep running, polling Tailscale every `poll_interval_secs` instead of exiting after one pass.
+    #[arg(long)]
+    daemon: bool
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 struct Config {
     tailnet_name: String,
     tailscale_token: String,
     pushover_token: String,
-    pushover_user_key: String
+    pushover_user_key: String,
+    smtp_host: String,
+    smtp_port: u16,
+    smtp_username: String,
+    smtp_password: String,
+    smtp_from: String,
+    smtp_to: String,
+    /// Which `NotificationSink`s to fan an alert out to, e.g. `["pushover", "desktop"]`.
+    enabled_backends: Vec<String>,
+    /// OAuth client credentials, used in place of `tailscale_token` when set.
+    /// Unlike API keys these don't expire after 90 days: the client exchanges
+    /// them for a short-lived access token and refreshes it automatically.
+    oauth_client_id: Option<String>,
+    oauth_client_secret: Option<String>,
+    /// Friendly names for devices, keyed by hostname, so alerts read e.g.
+    /// "Dana's laptop" instead of "desktop-a83fhe2". Device ids are not
+    /// looked up.
+    nicknames: HashMap<String, String>,
+    /// Default number of days before expiry we start warning at.
+    warn_days: i64,
+    /// Per-device overrides of `warn_days`, keyed by hostname (device ids are
+    /// not looked up), so critical nodes can warn earlier than the default.
+    warn_days_overrides: HashMap<String, i64>,
+    /// How often `--daemon` mode re-fetches and re-classifies devices.
+    poll_interval_secs: u64
 }
 
 impl ::std::default::Default for Config {
@@ -27,115 +61,207 @@ impl ::std::default::Default for Config {
             tailnet_name: String::new(),
             tailscale_token: String::new(),
             pushover_token: String::new(),
-            pushover_user_key: String::new()
+            pushover_user_key: String::new(),
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            smtp_from: String::new(),
+            smtp_to: String::new(),
+            enabled_backends: vec!["pushover".to_string()],
+            oauth_client_id: None,
+            oauth_client_secret: None,
+            nicknames: HashMap::new(),
+            warn_days: 15,
+            warn_days_overrides: HashMap::new(),
+            poll_interval_secs: 3600
         }
     }
 }
 
-mod date_format {
-    use chrono::DateTime;
-    use serde::{self, Deserialize, Deserializer};
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<chrono::Utc>, D::Error>
-    where 
-        D: Deserializer<'de>
-    {
-        let s = String::deserialize(deserializer)?;
-        let dt = DateTime::parse_from_rfc3339(&s).map_err(serde::de::Error::custom)?.with_timezone(&chrono::Utc);
-        Ok(dt)
+/// Build the set of `NotificationSink`s the user has enabled in `Config`.
+fn build_sinks(cfg: &Config) -> Vec<Box<dyn NotificationSink>> {
+    let mut sinks: Vec<Box<dyn NotificationSink>> = Vec::new();
+
+    for backend in &cfg.enabled_backends {
+        match backend.as_str() {
+            "pushover" => sinks.push(Box::new(PushoverSink::new(
+                cfg.pushover_token.clone(),
+                cfg.pushover_user_key.clone()
+            ))),
+            "smtp" => sinks.push(Box::new(SmtpSink::new(
+                cfg.smtp_host.clone(),
+                cfg.smtp_port,
+                cfg.smtp_username.clone(),
+                cfg.smtp_password.clone(),
+                cfg.smtp_from.clone(),
+                cfg.smtp_to.clone()
+            ))),
+            "desktop" => sinks.push(Box::new(DesktopSink::new())),
+            other => eprintln!("Unknown notification backend {:?}, ignoring", other)
+        }
     }
+
+    sinks
 }
 
-fn send_message(msg: &str, token: &str, user_key: &str){
-    use pushover::{ API, requests::message::SendMessage };
-    let api = API::new();
-    let msg_send = SendMessage::new(token, user_key, msg);
-    let response = api.send(&msg_send);
-    println!("{:?}", response.expect("Error sending message"));
+/// The friendly name to use for a device in notification messages, falling
+/// back to its hostname when no nickname is configured.
+fn get_nick<'a>(cfg: &'a Config, hostname: &'a str) -> &'a str {
+    cfg.nicknames.get(hostname).map(String::as_str).unwrap_or(hostname)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    use reqwest::{Client, IntoUrl, Response};
+/// The number of days before expiry to start warning for this device,
+/// honoring a per-device override over the configured default.
+fn warn_days_for(cfg: &Config, hostname: &str) -> i64 {
+    cfg.warn_days_overrides.get(hostname).copied().unwrap_or(cfg.warn_days)
+}
 
-    let cfg: Config = confy::load("tailscale_notifier", None)?;
+/// Classify a device's remaining lifetime into the bucket we alert on, so
+/// repeated runs can tell whether a device has crossed into a new one.
+fn expiry_bucket(days_until_expiration: i64) -> String {
+    if days_until_expiration < 0 {
+        "expired".to_string()
+    } else {
+        format!("expiring-{}-days", days_until_expiration)
+    }
+}
 
-    let file = confy::get_configuration_file_path("tailscale_notifier", None)?;
-    println!("Loading config from path: {:#?}", file);
+/// Send `msg` to every sink, returning whether at least one of them delivered
+/// it. Callers use this to decide whether the alert actually went out before
+/// recording it as acknowledged.
+fn send_message(sinks: &[Box<dyn NotificationSink>], msg: &str) -> bool {
+    let mut delivered = false;
 
-    async fn get<T: IntoUrl + Clone>(url: T, key: &str) -> reqwest::Result<Response> {
-        let header_value = format!("Bearer {}", key);
-        Client::builder()
-            .build()?
-            .get(url)
-            .header("Authorization", header_value)
-            .send()
-            .await
+    for sink in sinks {
+        match sink.send(msg) {
+            Ok(()) => delivered = true,
+            Err(err) => eprintln!("Error sending message: {err:?}")
+        }
     }
 
-    // Get the list of devices from Tailscale and deserialize the JSON into a struct
-    let url = format!("https://api.tailscale.com/api/v2/tailnet/{}/devices", &cfg.tailnet_name);
-
-    eprintln!("Fetching {url:?}...");
+    delivered
+}
 
-    let res = get(url, &cfg.tailscale_token).await?;
+/// Fetch the current devices, classify them, and notify on any that crossed
+/// into a new expiry bucket. This is the whole one-shot behavior of the tool;
+/// `--daemon` just calls it on a timer instead of once.
+///
+/// `tailscale` is built once by the caller and reused across cycles so its
+/// cached OAuth token survives between ticks instead of being refetched.
+async fn run_once(
+    cfg: &Config,
+    args: &Args,
+    state: &NotificationState,
+    tailscale: &TailscaleClient
+) -> Result<(), anyhow::Error> {
+    eprintln!("Fetching devices for tailnet {:?}...", cfg.tailnet_name);
 
-    let req_body = res.text().await?;
-    let devices: Vec<Device> = serde_json::from_str::<Devices>(&req_body)?.devices;
+    let devices = tailscale.devices().await?;
 
 
-    // Determine which devices are expiring within 15 days or have already expired
+    // Determine which devices are expiring within their warn window or have already expired
     let utc: chrono::DateTime<chrono::Utc> = chrono::Utc::now();
-    let mut devices_expiring: Vec<&Device> = Vec::new();
-    let mut devices_expired: Vec<&Device> = Vec::new();
+    // Devices whose alert bucket changed since last run (or --force was passed),
+    // paired with the bucket to persist once that device's notification has gone out.
+    let mut bucket_updates: Vec<(&Device, String)> = Vec::new();
 
     use std::cmp::Ordering;
 
     for device in devices.iter(){
         let days_until_expiration = (device.expires - utc).num_days();
 
-        if days_until_expiration < 15 {
+        if days_until_expiration < warn_days_for(cfg, &device.hostname) {
+            let bucket = expiry_bucket(days_until_expiration);
+            let already_alerted = state.last_bucket(&device.hostname).as_deref() == Some(bucket.as_str());
+
+            if !args.force && already_alerted {
+                println!("{} is still in bucket {:?}, skipping", device.hostname, bucket);
+                continue;
+            }
+
             match days_until_expiration.cmp(&(0_i64)) {
-                Ordering::Greater => {
-                    println!("{} expires in {} days", device.hostname, days_until_expiration);
-                    devices_expiring.push(device);
-                },
-                Ordering::Less => {
-                    println!("{} expired {} days ago", device.hostname, days_until_expiration.abs());
-                    devices_expired.push(device);
-                },
-                Ordering::Equal => {
-                    println!("{} expires today", device.hostname);
-                    devices_expiring.push(device);
-                }
+                Ordering::Greater => println!("{} expires in {} days", device.hostname, days_until_expiration),
+                Ordering::Less => println!("{} expired {} days ago", device.hostname, days_until_expiration.abs()),
+                Ordering::Equal => println!("{} expires today", device.hostname)
             }
+
+            bucket_updates.push((device, bucket));
         }
     }
 
-    // Send the push notification to my phone
-    if devices_expired.len() == 1 {
-        let device_name = &devices_expired[0].hostname;
-        let msg = format!("{} has expired!", device_name);
-        send_message(&msg, &cfg.pushover_token, &cfg.pushover_user_key);
-    } else if devices_expired.len() > 1 {
-        let msg = format!("{} devices are expired!", devices_expired.len());
-        send_message(&msg, &cfg.pushover_token, &cfg.pushover_user_key);
-    } else if devices_expiring.len() == 1 {
-        let device = devices_expired[0];
-        let device_name = &device.hostname;
+    if bucket_updates.is_empty() {
+        println!("No new expiry buckets to alert on");
+        return Ok(());
+    }
+
+    // Notify about each device individually, so a sink failure while alerting
+    // about one device can't swallow another device's alert, and so we only
+    // ever persist the bucket for the device a notification actually named.
+    let sinks = build_sinks(cfg);
+
+    for (device, bucket) in bucket_updates {
+        let device_name = get_nick(cfg, &device.hostname);
         let days_until_expiration = (device.expires - utc).num_days();
 
-        let msg = if days_until_expiration == 0 {
+        let msg = if days_until_expiration < 0 {
+            format!("{} has expired!", device_name)
+        } else if days_until_expiration == 0 {
             format!("{} is expiring today!", device_name)
         } else {
             format!("{} is expiring in {} days!", device_name, days_until_expiration)
         };
 
-        send_message(&msg, &cfg.pushover_token, &cfg.pushover_user_key);
-    } else {
-        let msg = format!("{} devices are expiring soon!", devices_expiring.len());
-        send_message(&msg, &cfg.pushover_token, &cfg.pushover_user_key);
+        if send_message(&sinks, &msg) {
+            state.set_bucket(&device.hostname, &bucket)?;
+        }
     }
 
     Ok(())
 }
+
+/// Run `run_once` forever on a `poll_interval_secs` tick, logging cycle timing
+/// and treating a single cycle's failure as non-fatal so a network blip
+/// doesn't kill a long-running service.
+async fn run_daemon(
+    cfg: &Config,
+    args: &Args,
+    state: &NotificationState,
+    tailscale: &TailscaleClient
+) -> Result<(), anyhow::Error> {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(cfg.poll_interval_secs));
+
+    loop {
+        ticker.tick().await;
+
+        let started = std::time::Instant::now();
+        if let Err(err) = run_once(cfg, args, state, tailscale).await {
+            eprintln!("Cycle failed, will retry next tick: {err:?}");
+        }
+        println!("Cycle finished in {:?}", started.elapsed());
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+
+    let cfg: Config = confy::load("tailscale_notifier", None)?;
+    let state = NotificationState::open()?;
+
+    let file = confy::get_configuration_file_path("tailscale_notifier", None)?;
+    println!("Loading config from path: {:#?}", file);
+
+    let tailscale = TailscaleClient::with_oauth(
+        cfg.tailnet_name.clone(),
+        cfg.tailscale_token.clone(),
+        cfg.oauth_client_id.clone(),
+        cfg.oauth_client_secret.clone()
+    )?;
+
+    if args.daemon {
+        run_daemon(&cfg, &args, &state, &tailscale).await
+    } else {
+        run_once(&cfg, &args, &state, &tailscale).await
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+
+/// A destination a notification message can be delivered to.
+///
+/// Implementations should treat `send` as fire-and-forget from the caller's
+/// perspective: any transport-level retry belongs inside the sink, and a
+/// returned `Err` means the message was not delivered.
+pub trait NotificationSink {
+    fn send(&self, msg: &str) -> Result<()>;
+}
+
+pub struct PushoverSink {
+    token: String,
+    user_key: String
+}
+
+impl PushoverSink {
+    pub fn new(token: String, user_key: String) -> Self {
+        Self { token, user_key }
+    }
+}
+
+impl NotificationSink for PushoverSink {
+    fn send(&self, msg: &str) -> Result<()> {
+        use pushover::{requests::message::SendMessage, API};
+
+        let api = API::new();
+        let msg_send = SendMessage::new(&self.token, &self.user_key, msg);
+        let response = api.send(&msg_send).context("sending Pushover message")?;
+        println!("{:?}", response);
+        Ok(())
+    }
+}
+
+pub struct SmtpSink {
+    host: String,
+    port: u16,
+    username: String,
+    password: String,
+    from: String,
+    to: String
+}
+
+impl SmtpSink {
+    pub fn new(host: String, port: u16, username: String, password: String, from: String, to: String) -> Self {
+        Self { host, port, username, password, from, to }
+    }
+}
+
+impl NotificationSink for SmtpSink {
+    fn send(&self, msg: &str) -> Result<()> {
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{Message, SmtpTransport, Transport};
+
+        let email = Message::builder()
+            .from(self.from.parse().context("parsing SMTP from address")?)
+            .to(self.to.parse().context("parsing SMTP to address")?)
+            .subject("Tailscale device expiry")
+            .body(msg.to_string())
+            .context("building notification email")?;
+
+        let creds = Credentials::new(self.username.clone(), self.password.clone());
+
+        // STARTTLS on the submission port (587), matching the default `smtp_port`.
+        let mailer = SmtpTransport::starttls_relay(&self.host)
+            .context("configuring SMTP relay")?
+            .port(self.port)
+            .credentials(creds)
+            .build();
+
+        mailer.send(&email).context("sending notification email")?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct DesktopSink;
+
+impl DesktopSink {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl NotificationSink for DesktopSink {
+    fn send(&self, msg: &str) -> Result<()> {
+        notify_rust::Notification::new()
+            .summary("Tailscale device expiry")
+            .body(msg)
+            .show()
+            .context("showing desktop notification")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+
+/// Tracks the last expiry bucket (e.g. `"expiring-10-days"`, `"expired"`) we
+/// alerted on for each device, so the same threshold doesn't trigger a fresh
+/// notification on every run.
+pub struct NotificationState {
+    tree: sled::Db,
+}
+
+impl NotificationState {
+    /// Open the on-disk store next to the confy config file.
+    pub fn open() -> Result<Self> {
+        let mut path = confy::get_configuration_file_path("tailscale_notifier", None)
+            .context("locating confy config directory")?;
+        path.set_file_name("notification_state");
+
+        let tree = sled::open(&path)
+            .with_context(|| format!("opening notification state store at {path:?}"))?;
+
+        Ok(Self { tree })
+    }
+
+    /// The bucket we last alerted on for `hostname`, if any.
+    pub fn last_bucket(&self, hostname: &str) -> Option<String> {
+        self.tree
+            .get(hostname)
+            .ok()
+            .flatten()
+            .map(|ivec| String::from_utf8_lossy(&ivec).into_owned())
+    }
+
+    /// Record that `hostname` was just alerted on for `bucket`.
+    pub fn set_bucket(&self, hostname: &str, bucket: &str) -> Result<()> {
+        self.tree
+            .insert(hostname, bucket.as_bytes())
+            .context("writing notification state")?;
+        self.tree.flush().context("flushing notification state")?;
+        Ok(())
+    }
+}